@@ -17,8 +17,15 @@
 //! Key pair (public + secrect) description
 
 use std::fmt;
+use std::sync::atomic;
+use ethereum_types::U256;
+use hmac::{Hmac, Mac, NewMac};
+use rand::rngs::OsRng;
+use rand::RngCore;
 use secp256k1::key;
+use sha2::Sha512;
 use super::{Secret, Public, Address, SECP256K1, Error};
+use super::secret::{SECP256K1_N, mod_add, mod_mul, secret_from_scalar, Share};
 use crate::Keccak256;
 
 /// Convert public key into the address
@@ -29,11 +36,66 @@ pub fn public_to_address(public: &Public) -> Address {
 	result
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Parses a SEC1-encoded public key (compressed or uncompressed) into a `Public`.
+pub fn public_from_slice(data: &[u8]) -> Result<Public, Error> {
+	let context = &SECP256K1;
+	let pub_key = key::PublicKey::from_slice(context, data).map_err(|_| Error::InvalidPublic)?;
+	let serialized = pub_key.serialize_vec(context, false);
+
+	let mut public = Public::default();
+	public.as_bytes_mut().copy_from_slice(&serialized[1..65]);
+	Ok(public)
+}
+
+fn random_scalar() -> U256 {
+	let mut bytes = [0u8; 32];
+	OsRng.fill_bytes(&mut bytes);
+	U256::from_big_endian(&bytes) % SECP256K1_N
+}
+
+/// Evaluates a polynomial (lowest-degree coefficient first) at `x`, modulo the group order.
+fn eval_polynomial(coefficients: &[U256], x: U256) -> U256 {
+	coefficients.iter().rev().fold(U256::zero(), |acc, &coefficient| mod_add(mod_mul(acc, x), coefficient))
+}
+
+/// Computes `HMAC-SHA512(key, data)`, as used by BIP32 child key derivation.
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+	let mut mac = Hmac::<Sha512>::new_varkey(key).expect("HMAC-SHA512 accepts keys of any length");
+	mac.update(data);
+
+	let mut out = [0u8; 64];
+	out.copy_from_slice(&mac.finalize().into_bytes());
+	out
+}
+
+#[derive(Debug, Clone)]
 /// secp256k1 key pair
 pub struct KeyPair {
 	secret: Secret,
 	public: Public,
+	/// BIP32 chain code, present only on pairs produced by `from_seed` or `derive_child`.
+	chain_code: Option<[u8; 32]>,
+}
+
+impl PartialEq for KeyPair {
+	fn eq(&self, other: &Self) -> bool {
+		// `Secret`'s own `PartialEq` already compares in constant time.
+		self.secret == other.secret && self.public == other.public && self.chain_code == other.chain_code
+	}
+}
+
+impl Drop for KeyPair {
+	fn drop(&mut self) {
+		// The secret itself scrubs its buffer via `Secret`'s own `Drop`. A leaked chain
+		// code plus the parent public key is enough to derive every non-hardened
+		// descendant, so it gets the same treatment here.
+		if let Some(ref mut chain_code) = self.chain_code {
+			for byte in chain_code.iter_mut() {
+				unsafe { std::ptr::write_volatile(byte, 0) };
+			}
+			atomic::compiler_fence(atomic::Ordering::SeqCst);
+		}
+	}
 }
 
 impl fmt::Display for KeyPair {
@@ -58,6 +120,7 @@ impl KeyPair {
 		let keypair = KeyPair {
 			secret: secret,
 			public: public,
+			chain_code: None,
 		};
 
 		Ok(keypair)
@@ -79,6 +142,7 @@ impl KeyPair {
 		KeyPair {
 			secret,
 			public,
+			chain_code: None,
 		}
 	}
 
@@ -96,12 +160,125 @@ impl KeyPair {
 	pub fn address(&self) -> Address {
 		public_to_address(&self.public)
 	}
+
+	/// Returns this pair's public key in uncompressed SEC1 form (`0x04` prefix).
+	pub fn public_uncompressed(&self) -> [u8; 65] {
+		let mut uncompressed = [0x04u8; 65];
+		uncompressed[1..].copy_from_slice(self.public.as_bytes());
+		uncompressed
+	}
+
+	/// Returns this pair's public key in compressed SEC1 form (`0x02`/`0x03` prefix).
+	pub fn public_compressed(&self) -> [u8; 33] {
+		let context = &SECP256K1;
+		// `self.public_uncompressed()` is this pair's own already-valid point, so
+		// re-parsing it here can't fail.
+		let pub_key = key::PublicKey::from_slice(context, &self.public_uncompressed())
+			.expect("a KeyPair's own public key is always a valid secp256k1 point");
+		let serialized = pub_key.serialize_vec(context, true);
+
+		let mut compressed = [0u8; 33];
+		compressed.copy_from_slice(&serialized);
+		compressed
+	}
+
+	/// Derives an ECDH shared secret with `peer`'s public key.
+	pub fn agree(&self, peer: &Public) -> Result<Secret, Error> {
+		let context = &SECP256K1;
+
+		let mut uncompressed = [0x04u8; 65];
+		uncompressed[1..].copy_from_slice(peer.as_bytes());
+		let mut shared = key::PublicKey::from_slice(context, &uncompressed).map_err(|_| Error::InvalidPublic)?;
+
+		let scalar = key::SecretKey::from_slice(context, &self.secret[..])?;
+		shared.mul_assign(context, &scalar[..])?;
+
+		let serialized = shared.serialize_vec(context, false);
+		Secret::import_key(&serialized[1..33])
+	}
+
+	/// Derives a BIP32 master key pair and chain code from a seed.
+	pub fn from_seed(seed: &[u8]) -> Result<KeyPair, Error> {
+		let i = hmac_sha512(b"Bitcoin seed", seed);
+		let (secret_bytes, chain_code_bytes) = i.split_at(32);
+
+		let mut keypair = KeyPair::from_secret(Secret::import_key(secret_bytes)?)?;
+		let mut chain_code = [0u8; 32];
+		chain_code.copy_from_slice(chain_code_bytes);
+		keypair.chain_code = Some(chain_code);
+
+		Ok(keypair)
+	}
+
+	/// Derives the BIP32 child key pair at `index` (hardened if `index >= 2^31`).
+	pub fn derive_child(&self, index: u32) -> Result<KeyPair, Error> {
+		let chain_code = self.chain_code.ok_or(Error::InvalidSecret)?;
+
+		let mut data = Vec::with_capacity(37);
+		if index >= 0x8000_0000 {
+			data.push(0);
+			data.extend_from_slice(&self.secret[..]);
+		} else {
+			// BIP32 CKD hashes `serP(point)`: the 33-byte compressed form, not the raw
+			// 64-byte X||Y coordinates stored in `self.public`.
+			data.extend_from_slice(&self.public_compressed());
+		}
+		data.extend_from_slice(&index.to_be_bytes());
+
+		let i = hmac_sha512(&chain_code, &data);
+		let (il, ir) = i.split_at(32);
+
+		let il_scalar = U256::from_big_endian(il);
+		if il_scalar >= SECP256K1_N {
+			return Err(Error::InvalidSecret);
+		}
+
+		let child_scalar = mod_add(il_scalar, U256::from_big_endian(&self.secret[..]));
+		if child_scalar.is_zero() {
+			return Err(Error::InvalidSecret);
+		}
+
+		let mut child = KeyPair::from_secret(secret_from_scalar(child_scalar)?)?;
+		let mut child_chain_code = [0u8; 32];
+		child_chain_code.copy_from_slice(ir);
+		child.chain_code = Some(child_chain_code);
+
+		Ok(child)
+	}
+
+	/// Splits this pair's secret into `n` Shamir shares with reconstruction threshold `t`.
+	pub fn split_secret(&self, t: usize, n: usize) -> Result<Vec<Share>, Error> {
+		if t == 0 || t > n {
+			return Err(Error::InvalidSecret);
+		}
+
+		let mut coefficients = Vec::with_capacity(t);
+		coefficients.push(U256::from_big_endian(&self.secret[..]));
+		for i in 1..t {
+			let coefficient = loop {
+				let candidate = random_scalar();
+				// A zero leading coefficient silently lowers the polynomial's degree,
+				// which would let `t - 1` shares reconstruct the secret.
+				if i + 1 < t || !candidate.is_zero() {
+					break candidate;
+				}
+			};
+			coefficients.push(coefficient);
+		}
+
+		(1..=n as u32)
+			.map(|index| {
+				let value = eval_polynomial(&coefficients, U256::from(index));
+				Ok(Share { index, threshold: t as u32, value: secret_from_scalar(value)? })
+			})
+			.collect()
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use std::str::FromStr;
-	use super::{KeyPair, Secret};
+	use super::{public_from_slice, public_to_address, KeyPair, Secret};
 
 	#[test]
 	fn from_secret() {
@@ -109,6 +286,79 @@ mod tests {
 		let _ = KeyPair::from_secret(secret).unwrap();
 	}
 
+	#[test]
+	fn keypair_equality_compares_secrets() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let other = Secret::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+
+		let a = KeyPair::from_secret(secret.clone()).unwrap();
+		let b = KeyPair::from_secret(secret).unwrap();
+		let c = KeyPair::from_secret(other).unwrap();
+
+		assert_eq!(a, b);
+		assert_ne!(a, c);
+	}
+
+	#[test]
+	fn agree_is_symmetric() {
+		let secret_a = Secret::from_str("00ba75e007f2bda16f5cbaa4e3abc9a2773e78665c46696c616ae8e0a9c878aa").unwrap();
+		let secret_b = Secret::from_str("905993f140f10d9b3c7c10c0a9bc29523234470ceb9d8592c4f471a498188190").unwrap();
+
+		let a = KeyPair::from_secret(secret_a).unwrap();
+		let b = KeyPair::from_secret(secret_b).unwrap();
+
+		let shared_a = a.agree(b.public()).unwrap();
+		let shared_b = b.agree(a.public()).unwrap();
+		assert_eq!(shared_a, shared_b);
+	}
+
+	#[test]
+	fn compressed_and_uncompressed_keys_share_an_address() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret).unwrap();
+
+		let compressed = kp.public_compressed();
+		assert!(compressed[0] == 0x02 || compressed[0] == 0x03);
+
+		let from_compressed = public_from_slice(&compressed).unwrap();
+		let from_uncompressed = public_from_slice(&kp.public_uncompressed()).unwrap();
+		assert_eq!(from_compressed, *kp.public());
+		assert_eq!(from_uncompressed, *kp.public());
+		assert_eq!(public_to_address(&from_compressed), kp.address());
+	}
+
+	#[test]
+	fn bip32_master_and_hardened_child_match_test_vector_1() {
+		let seed: [u8; 16] = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f];
+
+		let master = KeyPair::from_seed(&seed).unwrap();
+		assert_eq!(
+			format!("{:x}", master.secret()),
+			"e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+		);
+
+		let child = master.derive_child(0x8000_0000).unwrap();
+		assert_eq!(
+			format!("{:x}", child.secret()),
+			"edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+		);
+
+		// m/0H/1 is a non-hardened derivation, which hashes the parent's compressed
+		// public key rather than its secret.
+		let grandchild = child.derive_child(1).unwrap();
+		assert_eq!(
+			format!("{:x}", grandchild.secret()),
+			"3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+		);
+	}
+
+	#[test]
+	fn derive_child_requires_a_chain_code() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret).unwrap();
+		assert!(kp.derive_child(0).is_err());
+	}
+
 	#[test]
 	fn keypair_display() {
 		let expected =
@@ -119,4 +369,59 @@ address: 5b073e9233944b5e729e46d618f0d8edf3d9c34a".to_owned();
 		let kp = KeyPair::from_secret(secret).unwrap();
 		assert_eq!(format!("{}", kp), expected);
 	}
+
+	#[test]
+	fn split_secret_recovers_with_threshold_shares() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret.clone()).unwrap();
+
+		let shares = kp.split_secret(3, 5).unwrap();
+		assert_eq!(shares.len(), 5);
+
+		let recovered = Secret::recover_from_shares(&shares[1..4]).unwrap();
+		assert_eq!(recovered, secret);
+	}
+
+	#[test]
+	fn split_secret_recovers_from_any_threshold_subset() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret.clone()).unwrap();
+
+		let shares = kp.split_secret(3, 5).unwrap();
+		let subset = vec![shares[0].clone(), shares[2].clone(), shares[4].clone()];
+		let recovered = Secret::recover_from_shares(&subset).unwrap();
+		assert_eq!(recovered, secret);
+	}
+
+	#[test]
+	fn split_secret_rejects_invalid_threshold() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret).unwrap();
+
+		assert!(kp.split_secret(0, 5).is_err());
+		assert!(kp.split_secret(6, 5).is_err());
+	}
+
+	#[test]
+	fn recover_from_shares_rejects_too_few_shares() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret).unwrap();
+
+		let shares = kp.split_secret(3, 5).unwrap();
+		assert!(Secret::recover_from_shares(&shares[0..2]).is_err());
+	}
+
+	#[test]
+	fn recover_from_shares_rejects_duplicate_or_zero_index() {
+		let secret = Secret::from_str("a100df7a048e50ed308ea696dc600215098141cb391e9527329df289f9383f65").unwrap();
+		let kp = KeyPair::from_secret(secret).unwrap();
+
+		let shares = kp.split_secret(2, 3).unwrap();
+		let duplicated = vec![shares[0].clone(), shares[0].clone()];
+		assert!(Secret::recover_from_shares(&duplicated).is_err());
+
+		let mut zero_index = shares[0].clone();
+		zero_index.index = 0;
+		assert!(Secret::recover_from_shares(&[zero_index, shares[1].clone()]).is_err());
+	}
 }
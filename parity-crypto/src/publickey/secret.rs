@@ -0,0 +1,222 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity Ethereum.
+
+// Parity Ethereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Ethereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Ethereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! secp256k1 secret key
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic;
+use ethereum_types::{U256, U512};
+use secp256k1::key;
+use super::{Error, SECP256K1};
+
+#[derive(Debug, Clone)]
+/// secp256k1 secret key.
+///
+/// Compares in constant time and scrubs its backing buffer on drop, so key material
+/// doesn't leak through timing or linger in freed memory. Deliberately doesn't derive
+/// `Hash`/`Ord`, so it can't accidentally end up as a map/set key along a path that
+/// would leak comparison timing.
+pub struct Secret {
+	inner: [u8; 32],
+}
+
+impl Secret {
+	/// Imports and validates a 32-byte slice as a secret key.
+	pub fn import_key(slice: &[u8]) -> Result<Secret, Error> {
+		let context = &SECP256K1;
+		key::SecretKey::from_slice(context, slice).map_err(|_| Error::InvalidSecret)?;
+
+		let mut inner = [0u8; 32];
+		inner.copy_from_slice(slice);
+		Ok(Secret { inner })
+	}
+}
+
+impl PartialEq for Secret {
+	fn eq(&self, other: &Self) -> bool {
+		// No early return on the first differing byte: every byte is compared,
+		// so the running time doesn't depend on where (or whether) they differ.
+		let mut diff = 0u8;
+		for i in 0..self.inner.len() {
+			let x = unsafe { std::ptr::read_volatile(&self.inner[i]) };
+			let y = unsafe { std::ptr::read_volatile(&other.inner[i]) };
+			diff |= x ^ y;
+		}
+		diff == 0
+	}
+}
+
+impl Eq for Secret {}
+
+impl Drop for Secret {
+	fn drop(&mut self) {
+		for byte in self.inner.iter_mut() {
+			unsafe { std::ptr::write_volatile(byte, 0) };
+		}
+		atomic::compiler_fence(atomic::Ordering::SeqCst);
+	}
+}
+
+impl Deref for Secret {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.inner
+	}
+}
+
+impl From<key::SecretKey> for Secret {
+	fn from(key: key::SecretKey) -> Secret {
+		let mut inner = [0u8; 32];
+		inner.copy_from_slice(&key[..]);
+		Secret { inner }
+	}
+}
+
+impl FromStr for Secret {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.len() != 64 {
+			return Err(Error::InvalidSecret);
+		}
+
+		let mut bytes = [0u8; 32];
+		for (i, byte) in bytes.iter_mut().enumerate() {
+			*byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| Error::InvalidSecret)?;
+		}
+
+		Secret::import_key(&bytes)
+	}
+}
+
+impl fmt::LowerHex for Secret {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for byte in &self.inner {
+			write!(f, "{:02x}", byte)?;
+		}
+		Ok(())
+	}
+}
+
+/// Order of the secp256k1 group; all Shamir share arithmetic is done modulo this value.
+pub(crate) const SECP256K1_N: U256 = U256([
+	0xbfd25e8cd0364141,
+	0xbaaedce6af48a03b,
+	0xfffffffffffffffe,
+	0xffffffffffffffff,
+]);
+
+pub(crate) fn mod_add(a: U256, b: U256) -> U256 {
+	let sum = U512::from(a) + U512::from(b);
+	U256::try_from(sum % U512::from(SECP256K1_N)).expect("value was just reduced modulo a U256")
+}
+
+pub(crate) fn mod_sub(a: U256, b: U256) -> U256 {
+	if a >= b {
+		a - b
+	} else {
+		(SECP256K1_N - b) + a
+	}
+}
+
+pub(crate) fn mod_mul(a: U256, b: U256) -> U256 {
+	let product = U512::from(a) * U512::from(b);
+	U256::try_from(product % U512::from(SECP256K1_N)).expect("value was just reduced modulo a U256")
+}
+
+fn mod_pow(mut base: U256, mut exponent: U256, modulus: U256) -> U256 {
+	let mut result = U256::one();
+	base %= modulus;
+	while !exponent.is_zero() {
+		if exponent.bit(0) {
+			result = mod_mul(result, base);
+		}
+		exponent >>= 1;
+		base = mod_mul(base, base);
+	}
+	result
+}
+
+/// Modular inverse of `a` modulo the (prime) secp256k1 group order, via Fermat's little theorem.
+fn mod_inverse(a: U256) -> U256 {
+	mod_pow(a, SECP256K1_N - U256::from(2), SECP256K1_N)
+}
+
+pub(crate) fn secret_from_scalar(value: U256) -> Result<Secret, Error> {
+	let mut bytes = [0u8; 32];
+	value.to_big_endian(&mut bytes);
+	Secret::import_key(&bytes)
+}
+
+/// A single Shamir share of a split `Secret`, produced by `KeyPair::split_secret`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Share {
+	/// The `x` coordinate of this share; always in `1..=n` for an `n`-share split.
+	pub index: u32,
+	/// The reconstruction threshold `t` this share was generated with.
+	pub threshold: u32,
+	/// The `y = f(index) mod n` coordinate.
+	pub value: Secret,
+}
+
+impl Secret {
+	/// Reconstructs a secret from Shamir shares via Lagrange interpolation.
+	pub fn recover_from_shares(shares: &[Share]) -> Result<Secret, Error> {
+		let threshold = shares.first().ok_or(Error::InvalidSecret)?.threshold as usize;
+		if shares.iter().any(|share| share.threshold as usize != threshold) {
+			return Err(Error::InvalidSecret);
+		}
+		if shares.len() < threshold {
+			return Err(Error::InvalidSecret);
+		}
+
+		let mut seen_indices = HashSet::new();
+		for share in shares {
+			if share.index == 0 {
+				return Err(Error::InvalidSecret);
+			}
+			if !seen_indices.insert(share.index) {
+				return Err(Error::InvalidSecret);
+			}
+		}
+
+		let mut secret = U256::zero();
+		for (j, share_j) in shares.iter().enumerate() {
+			let x_j = U256::from(share_j.index);
+			let y_j = U256::from_big_endian(&share_j.value[..]);
+
+			let mut numerator = U256::one();
+			let mut denominator = U256::one();
+			for (k, share_k) in shares.iter().enumerate() {
+				if j == k {
+					continue;
+				}
+				let x_k = U256::from(share_k.index);
+				numerator = mod_mul(numerator, x_k);
+				denominator = mod_mul(denominator, mod_sub(x_k, x_j));
+			}
+
+			let lagrange_coefficient = mod_mul(numerator, mod_inverse(denominator));
+			secret = mod_add(secret, mod_mul(y_j, lagrange_coefficient));
+		}
+
+		secret_from_scalar(secret)
+	}
+}